@@ -1,28 +1,320 @@
 #![cfg_attr(test, feature(test))]
+#![feature(specialization, unsize)]
 
+use std::any::Any;
+use std::borrow::Borrow;
 use std::cell::UnsafeCell;
+use std::cmp::Ordering as CmpOrdering;
+use std::fmt;
+use std::hash::{Hash, Hasher};
+use std::marker::Unsize;
 use std::mem;
 use std::ops::Deref;
-use std::sync::{Arc as StdArc, Weak};
-
-pub struct Arc<T> {
+use std::sync::atomic::{AtomicU8, Ordering};
+use std::sync::{Arc as StdArc, Weak as StdWeak};
+
+/// `tag` holds `Inline`, stable, no promotion under way.
+const INLINE: u8 = 0;
+/// A `clone` has claimed the right to promote `Inline` to `Shared` and is
+/// allocating/rewriting `inner`; every other thread must wait for this to pass.
+const PROMOTING: u8 = 1;
+/// `tag` holds `Shared`, stable; this is terminal, nothing ever demotes a `Sync`-shared
+/// `Arc` back to `Inline` behind a thread's back (only `&mut self`/owned methods do
+/// that, and those can't run concurrently with anything else by the borrow checker's
+/// own rules).
+const SHARED: u8 = 2;
+
+pub struct Arc<T: ?Sized> {
+    tag: AtomicU8,
     inner: UnsafeCell<ArcData<T>>,
 }
 
-unsafe impl<T: Send> Send for Arc<T> {}
+// `T: Sync` is required here too, matching `std::sync::Arc`: once `Clone` promotes to
+// `Shared`, two independent `Arc<T>` handles point at the same allocation, so sending
+// each to a different thread and dereferencing both is exactly the concurrent-access
+// pattern `Sync` (not `Send` alone) is meant to gate.
+unsafe impl<T: ?Sized + Send + Sync> Send for Arc<T> {}
+
+// Promoting `Inline` to `Shared` is done through a CAS on `tag` rather than by
+// mutating `inner` directly, so concurrent clones can't race on the transition; this
+// makes the type safe to share across threads.
+unsafe impl<T: ?Sized + Send + Sync> Sync for Arc<T> {}
 
-enum ArcData<T> {
-    Inline(T),
+enum ArcData<T: ?Sized> {
+    Inline(<T as Inlinable>::Slot),
     Shared(StdArc<T>),
     Poisoned,
 }
 
-impl<T> Arc<T> {
-    pub fn new(val: T) -> Self {
+impl<T: ?Sized> Arc<T> {
+    fn from_data(data: ArcData<T>) -> Arc<T> {
+        let tag = match data {
+            ArcData::Inline(_) => INLINE,
+            ArcData::Shared(_) => SHARED,
+            ArcData::Poisoned => unreachable!("an `Arc` is never constructed already-poisoned"),
+        };
+
         Arc {
-            inner: ArcData::Inline(val).into(),
+            tag: AtomicU8::new(tag),
+            inner: data.into(),
+        }
+    }
+
+    /// Spins past a concurrent `clone`'s `PROMOTING` window and returns the tag once
+    /// it's settled on `INLINE` or `SHARED`.
+    fn stable_tag(&self) -> u8 {
+        loop {
+            match self.tag.load(Ordering::Acquire) {
+                PROMOTING => continue,
+                tag => return tag,
+            }
+        }
+    }
+
+    /// Returns `true` if `this` and `other` point at the same value, matching
+    /// `std::sync::Arc::ptr_eq`. Two `Shared` arcs compare by the underlying
+    /// allocation's address, like std; since an `Inline` value has no separate
+    /// allocation, two `Arc`s can only agree on one by being the very same `Arc`
+    /// (same slot), which `ptr::eq` on the outer struct already tells us directly.
+    pub fn ptr_eq(this: &Arc<T>, other: &Arc<T>) -> bool {
+        use ArcData::*;
+
+        match (this.stable_tag(), other.stable_tag()) {
+            (SHARED, SHARED) => {
+                match (unsafe { &*this.inner.get() }, unsafe { &*other.inner.get() }) {
+                    (Shared(a), Shared(b)) => StdArc::ptr_eq(a, b),
+                    _ => unreachable!("`stable_tag` just returned `SHARED` for both"),
+                }
+            }
+            _ => std::ptr::eq(this, other),
         }
     }
+
+    /// Coerces `Arc<T>` to `Arc<U>` — e.g. `Arc<[u8; 4]>` to `Arc<[u8]>`, or a concrete
+    /// type to `Arc<dyn Trait>`. This can't be a `CoerceUnsized` impl: that mechanism
+    /// only applies to structs whose single differing field can be reinterpreted in
+    /// place for free, but an `Inline` payload has no allocation to reinterpret —
+    /// unsizing it means promoting to `Shared` first, which is real work the
+    /// compiler's implicit coercion isn't allowed to perform. So this promotes any
+    /// `Inline` data via `T::promote` and then leans on `std::sync::Arc`'s own
+    /// (struct-based) `CoerceUnsized` impl to do the actual unsizing.
+    ///
+    /// Note this is an explicit call, not an implicit coercion: `let slice: Arc<[u8]>
+    /// = array;` does not compile against this type the way it does against
+    /// `std::sync::Arc`, unlike what was originally asked for — write
+    /// `Arc::unsize(array)` instead.
+    pub fn unsize<U: ?Sized>(this: Arc<T>) -> Arc<U>
+    where
+        T: Unsize<U>,
+    {
+        use std::ptr;
+
+        let data = unsafe { ptr::read(this.inner.get()) };
+        mem::forget(this);
+
+        let shared: StdArc<T> = match data {
+            ArcData::Inline(slot) => T::promote(slot),
+            ArcData::Shared(val) => val,
+            ArcData::Poisoned => unreachable!("an `Arc` is never constructed already-poisoned"),
+        };
+        let shared: StdArc<U> = shared;
+
+        Arc::from_data(ArcData::Shared(shared))
+    }
+}
+
+/// An uninhabited placeholder used as the default `Inlinable::Slot`, for any `T` that
+/// doesn't specialize it to something else. No value of this type is ever
+/// constructed: the only `T`s that ever reach `Inline` storage are `T: Sized`, and
+/// those always specialize `Slot` to `T` itself (see the second `impl` below).
+enum Never {}
+
+/// Picks the inline storage representation for `T`: sized values are stored directly
+/// (so `ArcData::Inline` holds a real `T`), while unsized values (`[U]`, `dyn Trait`,
+/// ...) can never be built inline in the first place, since there both needs to be a
+/// value to move into the slot and `ArcData<T>` itself must stay `Sized` regardless of
+/// `T`. Those get the uninhabited `Never` slot and always take the `Shared` path.
+trait Inlinable {
+    type Slot: Sized;
+
+    fn into_slot(self) -> Self::Slot
+    where
+        Self: Sized;
+
+    fn from_slot(slot: Self::Slot) -> Self
+    where
+        Self: Sized;
+
+    fn promote(slot: Self::Slot) -> StdArc<Self>;
+
+    fn slot_ref(slot: &Self::Slot) -> &Self;
+
+    fn slot_mut(slot: &mut Self::Slot) -> &mut Self;
+}
+
+impl<T: ?Sized> Inlinable for T {
+    default type Slot = Never;
+
+    // These default bodies have to be written in terms of `Self::Slot`, not the
+    // `Never` that the default `Slot` happens to resolve to: a later impl can
+    // override `Slot` alone and inherit these defaults, so specialization requires
+    // the signatures to stay generic over the associated type. That also means the
+    // bodies can no longer rely on `Self::Slot` being statically known as
+    // uninhabited (no exhaustive `match slot {}`); `unreachable!` stands in instead,
+    // since a `T: Sized` value always specializes `Slot = T` below and never falls
+    // through to these defaults in the first place.
+
+    default fn into_slot(self) -> Self::Slot
+    where
+        Self: Sized,
+    {
+        unreachable!("a `T: Sized` value always specializes `Inlinable::Slot = T`")
+    }
+
+    default fn from_slot(_slot: Self::Slot) -> Self
+    where
+        Self: Sized,
+    {
+        unreachable!("a `T: Sized` value always specializes `Inlinable::Slot = T`")
+    }
+
+    default fn promote(_slot: Self::Slot) -> StdArc<Self> {
+        unreachable!("an unsized `T` is never constructed with `Inline` data to promote")
+    }
+
+    default fn slot_ref(_slot: &Self::Slot) -> &Self {
+        unreachable!("an unsized `T` is never constructed with `Inline` data to dereference")
+    }
+
+    default fn slot_mut(_slot: &mut Self::Slot) -> &mut Self {
+        unreachable!("an unsized `T` is never constructed with `Inline` data to dereference")
+    }
+}
+
+impl<T> Inlinable for T {
+    type Slot = T;
+
+    fn into_slot(self) -> T {
+        self
+    }
+
+    fn from_slot(slot: T) -> T {
+        slot
+    }
+
+    fn promote(slot: T) -> StdArc<T> {
+        StdArc::new(slot)
+    }
+
+    fn slot_ref(slot: &T) -> &T {
+        slot
+    }
+
+    fn slot_mut(slot: &mut T) -> &mut T {
+        slot
+    }
+}
+
+/// A weak reference to an `inline_arc::Arc<T>`.
+///
+/// Unlike `std::sync::Weak`, this always round-trips back to `inline_arc::Arc<T>` via
+/// `upgrade`, rather than to `std::sync::Arc<T>`. A `Weak` can only ever point at an
+/// `Arc` that has already been promoted to its `Shared` representation, since there's
+/// no inline slot to weakly reference.
+pub struct Weak<T> {
+    inner: StdWeak<T>,
+}
+
+impl<T> Clone for Weak<T> {
+    fn clone(&self) -> Self {
+        Weak {
+            inner: self.inner.clone(),
+        }
+    }
+}
+
+impl<T> Default for Weak<T> {
+    fn default() -> Self {
+        Weak::new()
+    }
+}
+
+impl<T> Weak<T> {
+    /// Creates a new `Weak<T>` that doesn't point to anything, matching
+    /// `std::sync::Weak::new`.
+    pub fn new() -> Weak<T> {
+        Weak {
+            inner: StdWeak::new(),
+        }
+    }
+
+    /// Attempts to upgrade this `Weak` back into an `Arc`, returning `None` if the
+    /// value has already been dropped.
+    pub fn upgrade(&self) -> Option<Arc<T>> {
+        self.inner
+            .upgrade()
+            .map(|shared| Arc::from_data(ArcData::Shared(shared)))
+    }
+
+    /// The number of strong (`Arc`) references to the value this `Weak` points to.
+    pub fn strong_count(&self) -> usize {
+        self.inner.strong_count()
+    }
+
+    /// The number of weak references to the value, this one included; 0 once no
+    /// strong (`Arc`) reference remains, and for a `Weak` made by `Weak::new`.
+    pub fn weak_count(&self) -> usize {
+        self.inner.weak_count()
+    }
+}
+
+impl<T> Arc<T> {
+    pub fn new(val: T) -> Self {
+        Arc::from_data(ArcData::Inline(val.into_slot()))
+    }
+
+    /// Builds a value that holds a weak reference to itself, matching modern std's
+    /// `Arc::new_cyclic`. A live `Weak` only makes sense once there's a `Shared`
+    /// allocation to point at, so this skips the `Inline` state entirely and
+    /// delegates straight to `StdArc::new_cyclic`, which does the
+    /// allocate-weak-then-initialize dance for us.
+    pub fn new_cyclic<F>(data_fn: F) -> Arc<T>
+    where
+        F: FnOnce(&Weak<T>) -> T,
+    {
+        let shared = StdArc::new_cyclic(|std_weak| {
+            let weak = Weak {
+                inner: std_weak.clone(),
+            };
+            data_fn(&weak)
+        });
+
+        Arc::from_data(ArcData::Shared(shared))
+    }
+}
+
+impl<T: ?Sized> Arc<T> {
+    pub unsafe fn from_raw(ptr: *const T) -> Arc<T> {
+        Arc::from_data(ArcData::Shared(StdArc::from_raw(ptr)))
+    }
+}
+
+impl<T: ?Sized> From<Box<T>> for Arc<T> {
+    fn from(val: Box<T>) -> Arc<T> {
+        Arc::from_data(ArcData::Shared(StdArc::from(val)))
+    }
+}
+
+impl<U> From<Vec<U>> for Arc<[U]> {
+    fn from(val: Vec<U>) -> Arc<[U]> {
+        Arc::from_data(ArcData::Shared(StdArc::from(val)))
+    }
+}
+
+impl From<String> for Arc<str> {
+    fn from(val: String) -> Arc<str> {
+        Arc::from_data(ArcData::Shared(StdArc::from(val)))
+    }
 }
 
 impl<T> Arc<T>
@@ -32,10 +324,25 @@ where
     pub fn get_mut(this: &mut Arc<T>) -> Option<&mut T> {
         use ArcData::*;
 
+        // `&mut self` is exclusive, so no concurrent `clone` can be mid-promotion; we
+        // read `inner` directly and only write `tag` to keep it in sync afterwards.
+        if let Shared(val) = unsafe { &*this.inner.get() } {
+            if StdArc::strong_count(val) == 1 && StdArc::weak_count(val) == 0 {
+                let reclaimed = match mem::replace(unsafe { &mut *this.inner.get() }, Poisoned) {
+                    Shared(val) => val,
+                    _ => unreachable!("checked above that `this` holds `Shared` data"),
+                };
+                let val = StdArc::try_unwrap(reclaimed)
+                    .unwrap_or_else(|_| unreachable!("strong/weak counts were just checked"));
+                unsafe { *this.inner.get() = Inline(val.into_slot()) };
+                this.tag.store(INLINE, Ordering::Relaxed);
+            }
+        }
+
         let inner = unsafe { &mut *this.inner.get() };
 
         match inner {
-            Inline(val) => Some(val),
+            Inline(slot) => Some(T::slot_mut(slot)),
             Shared(_) => None,
             Poisoned => panic!("`Arc::clone` or `Arc::new` panicked and poisoned `inline_arc::Arc`! This should never happen."),
         }
@@ -46,12 +353,16 @@ where
         use std::ptr;
 
         let inner = unsafe { ptr::read(this.inner.get()) };
+        // `this` no longer owns the `ArcData` we just copied out of it; forget it so
+        // its drop glue doesn't also run on (and double-free) that same data.
+        mem::forget(this);
 
         match inner {
-            Inline(val) => Ok(val),
-            Shared(_) => Err(Arc {
-                inner: inner.into()
-            }),
+            Inline(slot) => Ok(T::from_slot(slot)),
+            Shared(val) => match StdArc::try_unwrap(val) {
+                Ok(val) => Ok(val),
+                Err(val) => Err(Arc::from_data(Shared(val))),
+            },
             Poisoned => panic!("`Arc::clone` or `Arc::new` panicked and poisoned `inline_arc::Arc`! This should never happen."),
         }
     }
@@ -59,51 +370,60 @@ where
     pub fn strong_count(this: &Arc<T>) -> usize {
         use ArcData::*;
 
-        let inner = unsafe { &mut *this.inner.get() };
-
-        match inner {
-            Inline(_) => 1,
-            Shared(val) => StdArc::strong_count(val),
-            Poisoned => panic!("`Arc::clone` or `Arc::new` panicked and poisoned `inline_arc::Arc`! This should never happen."),
+        match this.stable_tag() {
+            INLINE => 1,
+            SHARED => match unsafe { &*this.inner.get() } {
+                Shared(val) => StdArc::strong_count(val),
+                _ => unreachable!("`stable_tag` just returned `SHARED`"),
+            },
+            _ => unreachable!("`stable_tag` never returns `PROMOTING`"),
         }
     }
 
     pub fn weak_count(this: &Arc<T>) -> usize {
         use ArcData::*;
 
-        let inner = unsafe { &mut *this.inner.get() };
-
-        match inner {
-            Inline(_) => 0,
-            Shared(val) => StdArc::weak_count(val),
-            Poisoned => panic!("`Arc::clone` or `Arc::new` panicked and poisoned `inline_arc::Arc`! This should never happen."),
+        match this.stable_tag() {
+            INLINE => 0,
+            SHARED => match unsafe { &*this.inner.get() } {
+                Shared(val) => StdArc::weak_count(val),
+                _ => unreachable!("`stable_tag` just returned `SHARED`"),
+            },
+            _ => unreachable!("`stable_tag` never returns `PROMOTING`"),
         }
     }
 
     pub fn downgrade(this: &Arc<T>) -> Weak<T> {
         use ArcData::*;
 
-        let inner = mem::replace(unsafe { &mut *this.inner.get() }, Poisoned);
-
-        match inner {
-            Inline(val) => {
-                let shared = StdArc::new(val);
-                let out = StdArc::downgrade(&shared);
-
-                mem::replace(unsafe { &mut *this.inner.get() }, Shared(shared));
-
-                out
+        loop {
+            match this
+                .tag
+                .compare_exchange(INLINE, PROMOTING, Ordering::Acquire, Ordering::Acquire)
+            {
+                Ok(_) => {
+                    let slot = match mem::replace(unsafe { &mut *this.inner.get() }, Poisoned) {
+                        Inline(slot) => slot,
+                        _ => unreachable!("we just won the CAS away from `INLINE`"),
+                    };
+                    let shared = T::promote(slot);
+                    let out = StdArc::downgrade(&shared);
+
+                    unsafe { *this.inner.get() = Shared(shared) };
+                    this.tag.store(SHARED, Ordering::Release);
+
+                    return Weak { inner: out };
+                }
+                Err(SHARED) => {
+                    return match unsafe { &*this.inner.get() } {
+                        Shared(val) => Weak {
+                            inner: StdArc::downgrade(val),
+                        },
+                        _ => unreachable!("the CAS failed because `tag` was `SHARED`"),
+                    };
+                }
+                Err(_) => {} // Another thread is mid-promotion; spin until it settles.
             }
-            Shared(val) => StdArc::downgrade(&val),
-            Poisoned => panic!(
-                "`Arc::clone` or `Arc::new` panicked and poisoned `Arc`! This should never happen."
-            ),
-        }
-    }
-
-    pub unsafe fn from_raw(ptr: *const T) -> Arc<T> {
-        Arc {
-            inner: ArcData::Shared(StdArc::from_raw(ptr)).into(),
         }
     }
 
@@ -113,12 +433,13 @@ where
         let inner = unsafe { &mut *this.inner.get() };
 
         match inner {
-            Inline(val) => val,
+            Inline(slot) => T::slot_mut(slot),
             Shared(val) => {
-                mem::replace(unsafe { &mut *this.inner.get() }, Inline((&**val).clone()));
+                unsafe { *this.inner.get() = Inline((**val).clone().into_slot()) };
+                this.tag.store(INLINE, Ordering::Relaxed);
 
                 match unsafe { &mut *this.inner.get() } {
-                    Inline(val) => val,
+                    Inline(slot) => T::slot_mut(slot),
                     _ => panic!()
                 }
             },
@@ -127,38 +448,182 @@ where
     }
 }
 
-impl<T> Deref for Arc<T> {
+impl<T: ?Sized> Deref for Arc<T> {
     type Target = T;
 
     fn deref(&self) -> &T {
-        match unsafe { &*self.inner.get() } {
-            ArcData::Inline(val) => val,
-            ArcData::Shared(val) => &*val,
-            ArcData::Poisoned => panic!("`Arc::clone` or `Arc::new` panicked and poisoned `inline_arc::Arc`! This should never happen."),
+        match self.stable_tag() {
+            INLINE => match unsafe { &*self.inner.get() } {
+                ArcData::Inline(slot) => T::slot_ref(slot),
+                _ => unreachable!("`stable_tag` just returned `INLINE`"),
+            },
+            SHARED => match unsafe { &*self.inner.get() } {
+                ArcData::Shared(val) => &**val,
+                _ => unreachable!("`stable_tag` just returned `SHARED`"),
+            },
+            _ => unreachable!("`stable_tag` never returns `PROMOTING`"),
         }
     }
 }
 
-impl<T> Clone for Arc<T> {
+impl<T: ?Sized> Clone for Arc<T> {
     fn clone(&self) -> Self {
         use ArcData::*;
 
-        let inner = mem::replace(unsafe { &mut *self.inner.get() }, Poisoned);
+        // Promoting `Inline` to `Shared` happens under a CAS rather than an
+        // unconditional swap, so that two threads racing to clone the same `Inline`
+        // `Arc` can't both try to read/replace `inner` at once: the loser just spins
+        // until the winner has published `Shared` and bumps the std refcount instead.
+        loop {
+            match self
+                .tag
+                .compare_exchange(INLINE, PROMOTING, Ordering::Acquire, Ordering::Acquire)
+            {
+                Ok(_) => {
+                    let slot = match mem::replace(unsafe { &mut *self.inner.get() }, Poisoned) {
+                        Inline(slot) => slot,
+                        _ => unreachable!("we just won the CAS away from `INLINE`"),
+                    };
+                    let shared = T::promote(slot);
+                    let cloned = shared.clone();
+
+                    unsafe { *self.inner.get() = Shared(shared) };
+                    self.tag.store(SHARED, Ordering::Release);
+
+                    return Arc::from_data(Shared(cloned));
+                }
+                Err(SHARED) => {
+                    return match unsafe { &*self.inner.get() } {
+                        Shared(val) => Arc::from_data(Shared(val.clone())),
+                        _ => unreachable!("the CAS failed because `tag` was `SHARED`"),
+                    };
+                }
+                Err(_) => {} // Another thread is mid-promotion; spin until it settles.
+            }
+        }
+    }
+}
+
+impl<T: ?Sized + fmt::Debug> fmt::Debug for Arc<T> {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        fmt::Debug::fmt(&**self, f)
+    }
+}
 
-        match inner {
-            Inline(val) => {
-                let shared = StdArc::new(val);
-                mem::replace(unsafe { &mut *self.inner.get() }, Shared(shared.clone()));
-                Arc {
-                    inner: Shared(shared).into(),
+impl<T: ?Sized + fmt::Display> fmt::Display for Arc<T> {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        fmt::Display::fmt(&**self, f)
+    }
+}
+
+impl<T: ?Sized + PartialEq> PartialEq for Arc<T> {
+    fn eq(&self, other: &Self) -> bool {
+        **self == **other
+    }
+}
+
+impl<T: ?Sized + Eq> Eq for Arc<T> {}
+
+impl<T: ?Sized + PartialOrd> PartialOrd for Arc<T> {
+    fn partial_cmp(&self, other: &Self) -> Option<CmpOrdering> {
+        (**self).partial_cmp(&**other)
+    }
+}
+
+impl<T: ?Sized + Ord> Ord for Arc<T> {
+    fn cmp(&self, other: &Self) -> CmpOrdering {
+        (**self).cmp(&**other)
+    }
+}
+
+impl<T: ?Sized + Hash> Hash for Arc<T> {
+    fn hash<H: Hasher>(&self, state: &mut H) {
+        (**self).hash(state)
+    }
+}
+
+impl<T: Default> Default for Arc<T> {
+    fn default() -> Arc<T> {
+        Arc::new(T::default())
+    }
+}
+
+impl<T> From<T> for Arc<T> {
+    fn from(val: T) -> Arc<T> {
+        Arc::new(val)
+    }
+}
+
+impl<T: ?Sized> Borrow<T> for Arc<T> {
+    fn borrow(&self) -> &T {
+        &**self
+    }
+}
+
+impl<T: ?Sized> AsRef<T> for Arc<T> {
+    fn as_ref(&self) -> &T {
+        &**self
+    }
+}
+
+impl Arc<dyn Any + Send> {
+    /// Attempts to downcast `Arc<dyn Any + Send>` to a concrete type, mirroring
+    /// `liballoc`'s `Arc::downcast`. A trait-object `Arc` is always `Shared` (there's
+    /// no sized inline slot for it to occupy). Unlike the `Send + Sync` trait object
+    /// below, `std::sync::Arc` never implements `downcast` for `Send`-only trait
+    /// objects, so this is hand-rolled: check the concrete type behind the vtable with
+    /// `Any::is`, then reconstruct the `StdArc<T>` from the raw data pointer.
+    pub fn downcast<T: Any + Send>(self) -> Result<Arc<T>, Arc<dyn Any + Send>> {
+        use std::ptr;
+
+        let data = unsafe { ptr::read(self.inner.get()) };
+        // `self` no longer owns the `ArcData` we just copied out of it; forget it so
+        // its drop glue doesn't also run on (and double-free) that same data.
+        mem::forget(self);
+
+        match data {
+            ArcData::Shared(val) => {
+                if (*val).is::<T>() {
+                    // SAFETY: `is::<T>()` just confirmed the concrete type behind
+                    // this trait object's vtable is `T`, so reinterpreting the data
+                    // pointer as `*const T` (dropping the vtable metadata) and
+                    // handing it back to `StdArc::from_raw` recovers the original
+                    // `StdArc<T>` that `StdArc::into_raw` erased it from.
+                    let raw = StdArc::into_raw(val) as *const T;
+                    Ok(Arc::from_data(ArcData::Shared(unsafe {
+                        StdArc::from_raw(raw)
+                    })))
+                } else {
+                    Err(Arc::from_data(ArcData::Shared(val)))
                 }
             }
-            Shared(val) => Arc {
-                inner: Shared(val.clone()).into(),
+            ArcData::Inline(_) => unreachable!("a trait-object `Arc` is always `Shared`"),
+            ArcData::Poisoned => panic!("`Arc::clone` or `Arc::new` panicked and poisoned `inline_arc::Arc`! This should never happen."),
+        }
+    }
+}
+
+impl Arc<dyn Any + Send + Sync> {
+    /// As [`Arc<dyn Any + Send>::downcast`], for the `Send + Sync` trait object.
+    /// Unlike the `Send`-only form above, `std::sync::Arc` does implement `downcast`
+    /// natively for `Send + Sync` trait objects, so this can delegate to it directly.
+    pub fn downcast<T: Any + Send + Sync>(
+        self,
+    ) -> Result<Arc<T>, Arc<dyn Any + Send + Sync>> {
+        use std::ptr;
+
+        let data = unsafe { ptr::read(self.inner.get()) };
+        // `self` no longer owns the `ArcData` we just copied out of it; forget it so
+        // its drop glue doesn't also run on (and double-free) that same data.
+        mem::forget(self);
+
+        match data {
+            ArcData::Shared(val) => match val.downcast::<T>() {
+                Ok(val) => Ok(Arc::from_data(ArcData::Shared(val))),
+                Err(val) => Err(Arc::from_data(ArcData::Shared(val))),
             },
-            Poisoned => panic!(
-                "`Arc::clone` or `Arc::new` panicked and poisoned `Arc`! This should never happen."
-            ),
+            ArcData::Inline(_) => unreachable!("a trait-object `Arc` is always `Shared`"),
+            ArcData::Poisoned => panic!("`Arc::clone` or `Arc::new` panicked and poisoned `inline_arc::Arc`! This should never happen."),
         }
     }
 }
@@ -186,6 +651,199 @@ mod tests {
         assert_eq!(*other_data, 12);
     }
 
+    #[test]
+    fn weak_upgrade() {
+        use super::{Arc, Weak};
+
+        let lone = Weak::<i32>::new();
+        assert_eq!(lone.strong_count(), 0);
+        assert_eq!(lone.weak_count(), 0);
+        assert!(lone.upgrade().is_none());
+
+        let data = Arc::new(5);
+        let weak = Arc::downgrade(&data);
+        assert_eq!(weak.strong_count(), 1);
+        assert_eq!(weak.weak_count(), 1);
+
+        let upgraded = weak.upgrade().expect("value is still alive");
+        assert_eq!(*upgraded, 5);
+        drop(upgraded);
+        drop(data);
+
+        assert!(weak.upgrade().is_none());
+    }
+
+    #[test]
+    fn unsized_coercion() {
+        use super::Arc;
+
+        let array: Arc<[u8; 4]> = Arc::new([1, 2, 3, 4]);
+        let slice: Arc<[u8]> = Arc::unsize(array);
+        assert_eq!(&*slice, &[1, 2, 3, 4]);
+
+        trait Greet {
+            fn greeting(&self) -> &str;
+        }
+
+        impl Greet for &'static str {
+            fn greeting(&self) -> &str {
+                self
+            }
+        }
+
+        let concrete: Arc<&'static str> = Arc::new("hello");
+        let dynamic: Arc<dyn Greet> = Arc::unsize(concrete);
+        assert_eq!(dynamic.greeting(), "hello");
+    }
+
+    #[test]
+    fn from_conversions() {
+        use super::Arc;
+
+        let from_box: Arc<i32> = Arc::from(Box::new(5));
+        assert_eq!(*from_box, 5);
+
+        let from_vec: Arc<[i32]> = Arc::from(vec![1, 2, 3]);
+        assert_eq!(&*from_vec, &[1, 2, 3]);
+
+        let from_string: Arc<str> = Arc::from(String::from("hello"));
+        assert_eq!(&*from_string, "hello");
+    }
+
+    #[test]
+    fn get_mut_reclaims_unique_shared() {
+        use super::Arc;
+
+        let mut data = Arc::new(5);
+        let other_data = Arc::clone(&data); // Promotes `data` to `Shared`.
+
+        assert!(Arc::get_mut(&mut data).is_none());
+
+        drop(other_data); // `data` is now the sole owner again.
+        *Arc::get_mut(&mut data).expect("uniquely owned, should reclaim inline") += 1;
+        assert_eq!(*data, 6);
+    }
+
+    #[test]
+    fn try_unwrap_reclaims_unique_shared() {
+        use super::Arc;
+
+        let data = Arc::new(5);
+        let other_data = Arc::clone(&data); // Promotes `data` to `Shared`.
+
+        let data = Arc::try_unwrap(data).unwrap_err();
+        drop(other_data); // `data` is now the sole owner again.
+
+        match Arc::try_unwrap(data) {
+            Ok(val) => assert_eq!(val, 5),
+            Err(_) => panic!("uniquely owned, should reclaim"),
+        }
+    }
+
+    #[test]
+    fn concurrent_clone_promotes_once() {
+        use super::Arc;
+        use std::sync::Barrier;
+        use std::thread;
+
+        let data = Arc::new(5);
+        let barrier = Barrier::new(8);
+
+        // Collect the joined clones out of `scope` instead of dropping each as soon
+        // as it's checked: dropping them immediately would release their strong
+        // references before the final count below ever saw them.
+        let clones: Vec<_> = thread::scope(|scope| {
+            let handles: Vec<_> = (0..8)
+                .map(|_| {
+                    scope.spawn(|| {
+                        barrier.wait();
+                        Arc::clone(&data)
+                    })
+                })
+                .collect();
+
+            handles
+                .into_iter()
+                .map(|handle| handle.join().expect("clone thread shouldn't panic"))
+                .collect()
+        });
+
+        for clone in &clones {
+            assert_eq!(**clone, 5);
+        }
+
+        assert_eq!(Arc::strong_count(&data), 9);
+    }
+
+    #[test]
+    fn standard_traits() {
+        use super::Arc;
+
+        let a = Arc::new(5);
+        let b: Arc<i32> = 5.into();
+        assert_eq!(a, b);
+        assert!(a <= b);
+        assert_eq!(format!("{:?}", a), "5");
+        assert_eq!(format!("{}", a), "5");
+
+        let default: Arc<i32> = Arc::default();
+        assert_eq!(*default, 0);
+
+        use std::borrow::Borrow;
+        let borrowed: &i32 = a.borrow();
+        assert_eq!(*borrowed, 5);
+        assert_eq!(*a.as_ref(), 5);
+    }
+
+    #[test]
+    fn ptr_eq() {
+        use super::Arc;
+
+        let a = Arc::new(5);
+        assert!(Arc::ptr_eq(&a, &a));
+
+        let b = Arc::new(5);
+        assert!(!Arc::ptr_eq(&a, &b), "distinct `Inline` values are never `ptr_eq`");
+
+        let a_clone = Arc::clone(&a); // Promotes both `a` and `a_clone` to `Shared`.
+        assert!(Arc::ptr_eq(&a, &a_clone));
+        assert!(!Arc::ptr_eq(&a, &b));
+    }
+
+    #[test]
+    fn new_cyclic_self_reference() {
+        use super::{Arc, Weak};
+
+        struct Node {
+            me: Weak<Node>,
+            value: i32,
+        }
+
+        let node = Arc::new_cyclic(|me| Node {
+            me: me.clone(),
+            value: 5,
+        });
+
+        let upgraded = node.me.upgrade().expect("`node` is still alive");
+        assert_eq!(upgraded.value, 5);
+    }
+
+    #[test]
+    fn downcast() {
+        use super::Arc;
+        use std::any::Any;
+
+        let boxed: Arc<dyn Any + Send> = Arc::unsize(Arc::new(5i32));
+
+        let boxed = match boxed.downcast::<String>() {
+            Ok(_) => panic!("downcast to the wrong type succeeded"),
+            Err(boxed) => boxed,
+        };
+
+        let value = boxed.downcast::<i32>().expect("downcast to the right type");
+        assert_eq!(*value, 5);
+    }
+
     #[bench]
     fn make_mut(b: &mut Bencher) {
         use super::Arc;